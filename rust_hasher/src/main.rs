@@ -1,49 +1,463 @@
-use num::{bigint::RandBigInt, BigUint, Num};
 use std::env;
-use std::ops::Range;
-use rand::thread_rng;
-use blake2s_simd::{many::{hash_many, HashManyJob}, Params};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use arrayvec::ArrayVec;
+use rand::{thread_rng, RngCore};
 
-fn main() {
-    let args = env::args().collect::<Vec<String>>();
-    let t: BigUint = BigUint::from_str_radix(format!("{:0<64}", "00000000abc").as_str(), 16).unwrap();
-    const BATCH: u32 = 256;
-    let PREFIX: &str = args[1].as_str();
-    let SUFFIX: &str = args[2].as_str();
-    let mut nonce: BigUint = thread_rng().gen_biguint(255) + (BigUint::from(1u32) << 255);
-    // let mut nonce: BigUint = BigUint::from(0u32);
-    let mut params = Params::new();
-    params.hash_length(32);
-    const LOOPS: Range<u32> = 0..BATCH;
-    let mut hashes: Vec<BigUint>;// = Vec::new();
-    let mut found: Vec<bool> = Vec::new();
-    // let mut queries: u64 = 0;
-    while !found.iter_mut().any(|b| *b) {
-        nonce += BATCH;
-        let mut binding: Vec<Vec<u8>> = LOOPS
-                .into_iter()
-                .map(|i| nonce.clone() + i)
-                .map(|n: BigUint| n.to_str_radix(16))
-                .map(|s| String::new() + PREFIX + &s[..] + &SUFFIX)
-                .map(|s| s.into_bytes())
-                .collect();
-        let mut jobs: Vec<HashManyJob> = binding
-                .iter_mut()
-                .map(|s| HashManyJob::new(&params, s))
+/// Default number of SIMD-width batches hashed per `hash_many` call.
+const DEFAULT_BATCH_MULTIPLIER: u32 = 32;
+
+/// Width of a nonce in bytes.
+const WORD: usize = 32;
+/// Width of a nonce rendered as lowercase hex.
+const NONCE_HEX: usize = WORD * 2;
+/// Largest supported digest, in bytes (BLAKE2b).
+const MAX_HASH: usize = 64;
+
+/// Capacity of a reusable message buffer: nonce hex plus room for the
+/// prefix/suffix around it.
+const MSG_CAP: usize = NONCE_HEX + 4096;
+type Message = ArrayVec<u8, MSG_CAP>;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Minimal ANSI colouring for the status line, keeping stderr readable while
+/// stdout stays reserved for the winning result.
+mod term {
+    pub fn cyan(s: &str) -> String {
+        format!("\x1b[36m{}\x1b[0m", s)
+    }
+    pub fn bold(s: &str) -> String {
+        format!("\x1b[1m{}\x1b[0m", s)
+    }
+    pub fn dim(s: &str) -> String {
+        format!("\x1b[2m{}\x1b[0m", s)
+    }
+}
+
+/// A winning candidate: the nonce that beat the target and its hash.
+struct Found {
+    nonce: [u8; WORD],
+    hash: Vec<u8>,
+}
+
+/// A SIMD hash backend: knows its lane count and can hash a batch of messages,
+/// returning the first lane whose digest beats the target. Implementors wrap a
+/// preconfigured `Params` (fixing the digest length) so the hot loop stays
+/// allocation-free apart from the per-batch job vector.
+trait Hasher: Clone + Send + 'static {
+    /// How many messages the current CPU hashes in parallel.
+    fn degree() -> usize;
+    /// Hash every message and return the index and digest of the first that
+    /// compares lexicographically below `target`.
+    fn find_winner(&self, messages: &[Message], target: &[u8]) -> Option<(usize, Vec<u8>)>;
+}
+
+#[derive(Clone)]
+struct Blake2s {
+    params: blake2s_simd::Params,
+}
+
+impl Blake2s {
+    fn new(hash_length: usize) -> Self {
+        let mut params = blake2s_simd::Params::new();
+        params.hash_length(hash_length);
+        Blake2s { params }
+    }
+}
+
+impl Hasher for Blake2s {
+    fn degree() -> usize {
+        blake2s_simd::many::degree()
+    }
+
+    fn find_winner(&self, messages: &[Message], target: &[u8]) -> Option<(usize, Vec<u8>)> {
+        use blake2s_simd::many::{hash_many, HashManyJob};
+        let mut jobs: Vec<HashManyJob> = messages
+                .iter()
+                .map(|m| HashManyJob::new(&self.params, &m[..]))
                 .collect();
         hash_many(jobs.iter_mut());
-        hashes = jobs
-                .iter_mut()
-                .map(|j| j.to_hash().as_array().clone())
-                .map(|h| BigUint::from_bytes_be(&h))
+        jobs.iter()
+            .position(|j| j.to_hash().as_bytes() < target)
+            .map(|idx| (idx, jobs[idx].to_hash().as_bytes().to_vec()))
+    }
+}
+
+#[derive(Clone)]
+struct Blake2b {
+    params: blake2b_simd::Params,
+}
+
+impl Blake2b {
+    fn new(hash_length: usize) -> Self {
+        let mut params = blake2b_simd::Params::new();
+        params.hash_length(hash_length);
+        Blake2b { params }
+    }
+}
+
+impl Hasher for Blake2b {
+    fn degree() -> usize {
+        blake2b_simd::many::degree()
+    }
+
+    fn find_winner(&self, messages: &[Message], target: &[u8]) -> Option<(usize, Vec<u8>)> {
+        use blake2b_simd::many::{hash_many, HashManyJob};
+        let mut jobs: Vec<HashManyJob> = messages
+                .iter()
+                .map(|m| HashManyJob::new(&self.params, &m[..]))
                 .collect();
-        found = hashes.iter()
-                      .map(|h| h < &t)
-                      .collect();
-        // queries += BATCH as u64;
-    }
-    let idx = found.iter().position(|&b| b).unwrap();
-    // println!("Nonce: {:0>64}\nHash:  {:0>64}\nQueries: {}", (nonce + idx).to_str_radix(16), hashes[idx].to_str_radix(16), queries);
-    println!("{}{:0>64}{}", PREFIX, (nonce + idx).to_str_radix(16), SUFFIX);
-    
-}
\ No newline at end of file
+        hash_many(jobs.iter_mut());
+        jobs.iter()
+            .position(|j| j.to_hash().as_bytes() < target)
+            .map(|idx| (idx, jobs[idx].to_hash().as_bytes().to_vec()))
+    }
+}
+
+/// The hash function selected on the CLI.
+#[derive(Clone, Copy)]
+enum Algorithm {
+    Blake2s,
+    Blake2b,
+}
+
+impl Algorithm {
+    /// Maximum (and default) digest length for this function, in bytes.
+    fn default_hash_length(self) -> usize {
+        match self {
+            Algorithm::Blake2s => 32,
+            Algorithm::Blake2b => 64,
+        }
+    }
+}
+
+/// Add a `u32` to a big-endian 256-bit counter in place, propagating the carry
+/// up toward the most-significant byte. Wraps on overflow past `2^256`; from a
+/// random base that point lies far beyond any realistic amount of work.
+fn add_u32(n: &mut [u8; WORD], add: u32) {
+    let mut carry = add as u64;
+    for byte in n.iter_mut().rev() {
+        if carry == 0 {
+            break;
+        }
+        let sum = *byte as u64 + (carry & 0xff);
+        *byte = sum as u8;
+        carry = (carry >> 8) + (sum >> 8);
+    }
+}
+
+/// Render a big-endian counter as lowercase hex into a preallocated buffer.
+fn hex_encode(n: &[u8; WORD], out: &mut [u8; NONCE_HEX]) {
+    for (i, byte) in n.iter().enumerate() {
+        out[2 * i] = HEX_DIGITS[(byte >> 4) as usize];
+        out[2 * i + 1] = HEX_DIGITS[(byte & 0xf) as usize];
+    }
+}
+
+/// Mine a disjoint stride of the nonce space on a single worker thread.
+///
+/// Worker `k` of `threads` starts at `base + k*batch` and advances by
+/// `threads*batch` every iteration, so no two workers ever hash the same
+/// input while the space is still covered densely. The worker returns as
+/// soon as it finds a winner or another worker sets `stop`.
+///
+/// The hot path does no per-candidate allocation (the backend still builds one
+/// job vector per batch): each candidate's message buffer is built
+/// once (prefix + nonce hex + suffix) and only the nonce region is overwritten
+/// between iterations, the nonce is a fixed `[u8; WORD]` counter incremented
+/// with `add_u32`, and each digest is compared against the target by a plain
+/// lexicographic `memcmp` rather than by constructing big integers.
+fn mine<H: Hasher>(
+    hasher: H,
+    worker: u32,
+    threads: u32,
+    batch: u32,
+    base: [u8; WORD],
+    target: Vec<u8>,
+    prefix: String,
+    suffix: String,
+    stop: Arc<AtomicBool>,
+    queries: Arc<AtomicU64>,
+    tx: mpsc::Sender<Found>,
+) {
+    let stride = threads * batch;
+    let mut nonce = base;
+    add_u32(&mut nonce, worker * batch);
+
+    // The nonce hex always lands at this offset inside every message buffer.
+    let prefix = prefix.into_bytes();
+    let suffix = suffix.into_bytes();
+    let nonce_at = prefix.len();
+
+    // Preallocate one reusable message buffer per lane; only the nonce region
+    // is rewritten each iteration.
+    let mut messages: Vec<Message> = (0..batch)
+            .map(|_| {
+                let mut buf = Message::new();
+                buf.try_extend_from_slice(&prefix).expect("prefix too long");
+                buf.try_extend_from_slice(&[0u8; NONCE_HEX]).expect("buffer overflow");
+                buf.try_extend_from_slice(&suffix).expect("suffix too long");
+                buf
+            })
+            .collect();
+
+    let mut hex = [0u8; NONCE_HEX];
+    let mut candidate;
+    while !stop.load(Ordering::Relaxed) {
+        for (i, msg) in messages.iter_mut().enumerate() {
+            candidate = nonce;
+            add_u32(&mut candidate, i as u32);
+            hex_encode(&candidate, &mut hex);
+            msg[nonce_at..nonce_at + NONCE_HEX].copy_from_slice(&hex);
+        }
+        let winner = hasher.find_winner(&messages, &target);
+        queries.fetch_add(batch as u64, Ordering::Relaxed);
+        if let Some((idx, hash)) = winner {
+            stop.store(true, Ordering::Relaxed);
+            let mut nonce = nonce;
+            add_u32(&mut nonce, idx as u32);
+            let _ = tx.send(Found { nonce, hash });
+            return;
+        }
+        add_u32(&mut nonce, stride);
+    }
+}
+
+/// Parse a hex string (up to `2*len` nibbles) into a big-endian target of
+/// `len` bytes, padding with zero nibbles on the least-significant side so the
+/// supplied nibbles fix the high bits of the threshold (matching the historical
+/// `"00000000abc"` difficulty default).
+fn parse_target(hex: &str, len: usize) -> Vec<u8> {
+    let nibbles = len * 2;
+    assert!(hex.len() <= nibbles, "target hex must be at most {} nibbles", nibbles);
+    let padded = format!("{:0<width$}", hex, width = nibbles);
+    (0..len)
+        .map(|i| u8::from_str_radix(&padded[2 * i..2 * i + 2], 16).expect("invalid target hex"))
+        .collect()
+}
+
+/// Build the threshold for "at least `n` leading zero bits", i.e. `2^(bits-n)`,
+/// as a big-endian target of `len` bytes with a single bit set. `n` ranges over
+/// `1..=bits` (all `bits` demands an all-zero hash).
+fn target_from_leading_zero_bits(n: u32, len: usize) -> Vec<u8> {
+    let bits = (len * 8) as u32;
+    assert!((1..=bits).contains(&n), "--leading-zero-bits must be in 1..={}", bits);
+    let bit = bits - n; // position of the set bit, counting from the LSB
+    let mut out = vec![0u8; len];
+    out[len - 1 - (bit / 8) as usize] = 1 << (bit % 8);
+    out
+}
+
+/// Interpret a big-endian target as an `f64` magnitude.
+fn target_as_f64(t: &[u8]) -> f64 {
+    t.iter().fold(0.0f64, |acc, &b| acc * 256.0 + b as f64)
+}
+
+/// Format a hashrate in H/s with a magnitude suffix.
+fn fmt_rate(rate: f64) -> String {
+    const UNITS: [&str; 5] = ["H/s", "kH/s", "MH/s", "GH/s", "TH/s"];
+    let mut r = rate;
+    let mut unit = 0;
+    while r >= 1000.0 && unit < UNITS.len() - 1 {
+        r /= 1000.0;
+        unit += 1;
+    }
+    format!("{:.2} {}", r, UNITS[unit])
+}
+
+/// Format a duration in seconds as `HhMmSs`, omitting leading zero units.
+fn fmt_secs(secs: f64) -> String {
+    if !secs.is_finite() {
+        return "∞".to_string();
+    }
+    let total = secs as u64;
+    let (h, m, s) = (total / 3600, (total % 3600) / 60, total % 60);
+    if h > 0 {
+        format!("{}h{:02}m{:02}s", h, m, s)
+    } else if m > 0 {
+        format!("{}m{:02}s", m, s)
+    } else {
+        format!("{}s", s)
+    }
+}
+
+/// Run the parallel search with a concrete backend and return the winner.
+fn run<H: Hasher>(
+    hasher: H,
+    threads: u32,
+    batch_multiplier: u32,
+    base: [u8; WORD],
+    target: Vec<u8>,
+    prefix: String,
+    suffix: String,
+    progress: bool,
+) -> Found {
+    // Fill the SIMD lanes exactly: the effective batch is the runtime lane
+    // count times the multiplier, so every `hash_many` call is a whole
+    // number of full-width passes with no wasted partial batch.
+    let batch = H::degree() as u32 * batch_multiplier.max(1);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let queries = Arc::new(AtomicU64::new(0));
+    let (tx, rx) = mpsc::channel();
+    let mut handles = Vec::with_capacity(threads as usize);
+    for worker in 0..threads {
+        let hasher = hasher.clone();
+        let target = target.clone();
+        let prefix = prefix.clone();
+        let suffix = suffix.clone();
+        let stop = Arc::clone(&stop);
+        let queries = Arc::clone(&queries);
+        let tx = tx.clone();
+        handles.push(thread::spawn(move || {
+            mine(hasher, worker, threads, batch, base, target, prefix, suffix, stop, queries, tx)
+        }));
+    }
+    drop(tx);
+
+    // Optional status reporter: hashrate, elapsed, queries and an ETA derived
+    // from the expected work `2^bits / t`. Printed to stderr so stdout keeps
+    // only the winning line.
+    let reporter = progress.then(|| {
+        let stop = Arc::clone(&stop);
+        let queries = Arc::clone(&queries);
+        let expected = 2f64.powi((target.len() * 8) as i32) / target_as_f64(&target);
+        thread::spawn(move || {
+            let start = Instant::now();
+            while !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_secs(1));
+                let done = queries.load(Ordering::Relaxed);
+                let elapsed = start.elapsed().as_secs_f64();
+                let rate = if elapsed > 0.0 { done as f64 / elapsed } else { 0.0 };
+                let eta = if rate > 0.0 {
+                    ((expected - done as f64).max(0.0)) / rate
+                } else {
+                    f64::INFINITY
+                };
+                eprintln!(
+                    "{} {}  elapsed {}  queries {}  eta {}",
+                    term::bold(&term::cyan(&fmt_rate(rate))),
+                    term::dim("├"),
+                    fmt_secs(elapsed),
+                    done,
+                    fmt_secs(eta),
+                );
+            }
+        })
+    });
+
+    // The first worker to win sends its result; the rest observe `stop` and exit.
+    let found = rx.recv().expect("all workers exited without finding a nonce");
+    for handle in handles {
+        let _ = handle.join();
+    }
+    if let Some(reporter) = reporter {
+        let _ = reporter.join();
+    }
+    found
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+
+    let mut prefix = String::new();
+    let mut suffix = String::new();
+    let mut target_hex: Option<String> = None;
+    let mut leading_zero_bits: Option<u32> = None;
+    let mut progress = false;
+    let mut algorithm = Algorithm::Blake2s;
+    let mut hash_length: Option<usize> = None;
+    let mut threads: u32 = thread::available_parallelism().map(|n| n.get() as u32).unwrap_or(1);
+    let mut batch_multiplier: u32 = DEFAULT_BATCH_MULTIPLIER;
+    let mut positional = 0;
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--threads" => {
+                i += 1;
+                threads = args[i].parse().expect("--threads expects a positive integer");
+            }
+            "--batch-multiplier" => {
+                i += 1;
+                batch_multiplier = args[i].parse().expect("--batch-multiplier expects a positive integer");
+            }
+            "--target-hex" => {
+                i += 1;
+                target_hex = Some(args[i].clone());
+            }
+            "--leading-zero-bits" => {
+                i += 1;
+                leading_zero_bits = Some(args[i].parse().expect("--leading-zero-bits expects an integer"));
+            }
+            "--progress" => {
+                progress = true;
+            }
+            "--algorithm" => {
+                i += 1;
+                algorithm = match args[i].as_str() {
+                    "blake2s" => Algorithm::Blake2s,
+                    "blake2b" => Algorithm::Blake2b,
+                    other => panic!("unknown --algorithm {:?} (expected blake2s or blake2b)", other),
+                };
+            }
+            "--hash-length" => {
+                i += 1;
+                hash_length = Some(args[i].parse().expect("--hash-length expects an integer"));
+            }
+            _ => {
+                match positional {
+                    0 => prefix = args[i].clone(),
+                    1 => suffix = args[i].clone(),
+                    _ => panic!("unexpected positional argument: {}", args[i]),
+                }
+                positional += 1;
+            }
+        }
+        i += 1;
+    }
+    let threads = threads.max(1);
+
+    let hash_length = hash_length.unwrap_or_else(|| algorithm.default_hash_length());
+    assert!(
+        (1..=algorithm.default_hash_length()).contains(&hash_length),
+        "--hash-length must be in 1..={} for the selected algorithm",
+        algorithm.default_hash_length()
+    );
+    debug_assert!(hash_length <= MAX_HASH);
+
+    // Exactly one difficulty specifier must be supplied. The threshold is as
+    // wide as the digest it is compared against.
+    let t = match (target_hex, leading_zero_bits) {
+        (Some(hex), None) => parse_target(&hex, hash_length),
+        (None, Some(n)) => target_from_leading_zero_bits(n, hash_length),
+        (None, None) => panic!("supply one of --target-hex or --leading-zero-bits"),
+        (Some(_), Some(_)) => panic!("supply only one of --target-hex or --leading-zero-bits"),
+    };
+
+    // Seed a shared base in the top half of the 256-bit space; each worker
+    // then carves out its own stride off this base.
+    let mut base = [0u8; WORD];
+    thread_rng().fill_bytes(&mut base);
+    base[0] |= 0x80;
+
+    let found = match algorithm {
+        Algorithm::Blake2s => run(
+            Blake2s::new(hash_length),
+            threads, batch_multiplier, base, t, prefix.clone(), suffix.clone(), progress,
+        ),
+        Algorithm::Blake2b => run(
+            Blake2b::new(hash_length),
+            threads, batch_multiplier, base, t, prefix.clone(), suffix.clone(), progress,
+        ),
+    };
+
+    let _ = &found.hash;
+    let mut hex = [0u8; NONCE_HEX];
+    hex_encode(&found.nonce, &mut hex);
+    let nonce_hex = std::str::from_utf8(&hex).expect("hex is ascii");
+    println!("{}{}{}", prefix, nonce_hex, suffix);
+}